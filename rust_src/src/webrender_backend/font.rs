@@ -2,7 +2,10 @@ use std::mem::ManuallyDrop;
 use std::ptr;
 
 use font_kit::{
-    family_name::FamilyName, loaders::default::Font, metrics::Metrics, properties::Properties,
+    family_name::FamilyName,
+    loaders::default::Font,
+    metrics::Metrics,
+    properties::{Properties, Stretch, Style, Weight},
     source::SystemSource,
 };
 
@@ -82,6 +85,158 @@ impl LispFontLike {
         }
     }
 
+    fn get_name(&self) -> Option<String> {
+        let tem = self.aref(font_property_index::FONT_NAME_INDEX);
+
+        if tem.is_nil() {
+            None
+        } else {
+            tem.as_string().map(|s| s.to_string())
+        }
+    }
+
+    fn set_symbol_field(&self, index: font_property_index::Type, field: &str) {
+        if field.is_empty() || field == "*" {
+            return;
+        }
+
+        self.aset(index, unsafe { Fmake_symbol(LispObject::from(field)) });
+    }
+
+    /// Parse an X Logical Font Description such as
+    /// `-*-DejaVu Sans Mono-bold-i-normal-*-14-*-*-*-*-*-iso10646-1` and
+    /// populate the corresponding `font_property_index` slots on this
+    /// entity. Returns `false` (leaving the entity untouched) if `name`
+    /// is not a well-formed XLFD string.
+    fn parse_xlfd(&self, name: &str) -> bool {
+        let fields = match split_xlfd_fields(name) {
+            Some(fields) => fields,
+            None => return false,
+        };
+
+        self.set_symbol_field(font_property_index::FONT_FOUNDRY_INDEX, &fields[0]);
+        self.set_symbol_field(font_property_index::FONT_FAMILY_INDEX, &fields[1]);
+        self.set_symbol_field(font_property_index::FONT_WEIGHT_INDEX, &fields[2]);
+        self.set_symbol_field(
+            font_property_index::FONT_SLANT_INDEX,
+            xlfd_slant_name(&fields[3]),
+        );
+        self.set_symbol_field(font_property_index::FONT_WIDTH_INDEX, &fields[4]);
+
+        if let Ok(pixel_size) = fields[6].parse::<i64>() {
+            self.aset(
+                font_property_index::FONT_SIZE_INDEX,
+                LispObject::from(pixel_size),
+            );
+        }
+
+        if fields[12] != "*" && fields[13] != "*" {
+            let registry = format!("{}-{}", fields[12], fields[13]);
+            self.set_symbol_field(font_property_index::FONT_REGISTRY_INDEX, &registry);
+        }
+
+        true
+    }
+
+    fn symbol_field_name(&self, index: font_property_index::Type) -> Option<String> {
+        let val = self.aref(index);
+
+        if val.is_nil() {
+            return None;
+        }
+
+        let symbol_or_string = val.as_symbol_or_string();
+        let string: LispStringRef = symbol_or_string.into();
+        Some(string.to_string())
+    }
+
+    fn set_fontconfig_property(&self, key: &str, value: &str) {
+        match key {
+            "weight" => self.set_symbol_field(font_property_index::FONT_WEIGHT_INDEX, value),
+            "slant" => self.set_symbol_field(font_property_index::FONT_SLANT_INDEX, value),
+            "width" => self.set_symbol_field(font_property_index::FONT_WIDTH_INDEX, value),
+            "spacing" => self.set_symbol_field(font_property_index::FONT_SPACING_INDEX, value),
+            "size" => {
+                if let Ok(size) = value.parse::<i64>() {
+                    self.aset(font_property_index::FONT_SIZE_INDEX, LispObject::from(size));
+                }
+            }
+            "dpi" => {
+                if let Ok(dpi) = value.parse::<i64>() {
+                    self.aset(font_property_index::FONT_DPI_INDEX, LispObject::from(dpi));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// A bare style keyword such as `bold` or `italic`, with no `key=`
+    /// prefix, resolves against whichever style table recognizes it.
+    fn set_fontconfig_keyword(&self, keyword: &str) {
+        if WEIGHT_TABLE.iter().any(|(name, _, _)| *name == keyword) {
+            self.set_symbol_field(font_property_index::FONT_WEIGHT_INDEX, keyword);
+        } else if SLANT_TABLE.iter().any(|(name, _)| *name == keyword) {
+            self.set_symbol_field(font_property_index::FONT_SLANT_INDEX, keyword);
+        } else if WIDTH_TABLE.iter().any(|(name, _)| *name == keyword) {
+            self.set_symbol_field(font_property_index::FONT_WIDTH_INDEX, keyword);
+        }
+    }
+
+    /// Parse a fontconfig-style name such as
+    /// `DejaVu Sans Mono:weight=bold:slant=italic:size=14` and populate
+    /// the corresponding `font_property_index` slots on this entity.
+    /// Returns `false` (leaving the entity untouched) if `name` has no
+    /// family segment.
+    fn parse_fontconfig(&self, name: &str) -> bool {
+        let mut segments = name.split(':');
+
+        let family = match segments.next() {
+            Some(family) if !family.is_empty() => family,
+            _ => return false,
+        };
+
+        self.set_symbol_field(font_property_index::FONT_FAMILY_INDEX, family);
+
+        for segment in segments {
+            match segment.find('=') {
+                Some(eq) => self.set_fontconfig_property(&segment[..eq], &segment[eq + 1..]),
+                None => self.set_fontconfig_keyword(segment),
+            }
+        }
+
+        true
+    }
+
+    /// The inverse of [`Self::parse_fontconfig`]: render this entity back
+    /// into a canonical fontconfig name, for display in the mode line and
+    /// `describe-font`. Only family, weight, slant, width and size are
+    /// written; spacing and dpi are not.
+    fn unparse_fontconfig(&self) -> String {
+        let mut name = self
+            .symbol_field_name(font_property_index::FONT_FAMILY_INDEX)
+            .unwrap_or_else(|| "*".to_string());
+
+        if let Some(weight) = self.symbol_field_name(font_property_index::FONT_WEIGHT_INDEX) {
+            name.push_str(&format!(":weight={}", weight));
+        }
+
+        if let Some(slant) = self.symbol_field_name(font_property_index::FONT_SLANT_INDEX) {
+            name.push_str(&format!(":slant={}", slant));
+        }
+
+        if let Some(width) = self.symbol_field_name(font_property_index::FONT_WIDTH_INDEX) {
+            name.push_str(&format!(":width={}", width));
+        }
+
+        if let Some(size) = self.aref(font_property_index::FONT_SIZE_INDEX).as_fixnum() {
+            if size > 0 {
+                name.push_str(&format!(":size={}", size));
+            }
+        }
+
+        name
+    }
+
     fn aset(&self, index: font_property_index::Type, val: LispObject) {
         let vl = self.0.as_vectorlike().unwrap();
         let mut v = unsafe { vl.as_vector_unchecked() };
@@ -108,15 +263,83 @@ extern "C" fn get_cache(f: *mut frame) -> LispObject {
     dpyinfo.name_list_element
 }
 
+/// Unpack a packed `0xRRGGBB` pixel value, as stored on `face.foreground`
+/// / `face.background`, into a WebRender color.
+fn pixel_to_colorf(pixel: u64) -> ColorF {
+    let r = ((pixel >> 16) & 0xff) as f32 / 255.0;
+    let g = ((pixel >> 8) & 0xff) as f32 / 255.0;
+    let b = (pixel & 0xff) as f32 / 255.0;
+
+    ColorF::new(r, g, b, 1.0)
+}
+
 extern "C" fn draw(
-    _s: *mut glyph_string,
-    _from: i32,
-    _to: i32,
-    _x: i32,
-    _y: i32,
-    _with_background: bool,
+    s: *mut glyph_string,
+    from: i32,
+    to: i32,
+    x: i32,
+    y: i32,
+    with_background: bool,
 ) -> i32 {
-    0
+    let s = ExternalPtr::new(s);
+
+    let frame: LispFrameRef = s.f.into();
+    let output: OutputRef = unsafe { frame.output_data.wr.into() };
+
+    let font: WRFontRef = s.font.into();
+    let face = s.face;
+
+    let scale = font.font.pixel_size as f32 / font.metrics.units_per_em as f32;
+
+    let from = from as usize;
+    let to = to as usize;
+    let char2b = unsafe { std::slice::from_raw_parts(s.char2b, to) };
+
+    let mut glyph_instances = Vec::with_capacity(to - from);
+    let mut advance_x = x as f32;
+
+    for &code in &char2b[from..to] {
+        let glyph_id = code as u32;
+
+        glyph_instances.push(GlyphInstance {
+            index: glyph_id,
+            point: LayoutPoint::new(advance_x, y as f32),
+        });
+
+        let glyph_advance = font
+            .font_backend
+            .advance(glyph_id)
+            .map(|a| a.x() * scale)
+            .unwrap_or(font.font.average_width as f32);
+
+        advance_x += glyph_advance;
+    }
+
+    let bounds = LayoutRect::new(
+        LayoutPoint::new(x as f32, (y - font.font.ascent) as f32),
+        LayoutSize::new(advance_x - x as f32, font.font.height as f32),
+    );
+    let common = CommonItemProperties::new(bounds, output.space_and_clip());
+
+    if with_background {
+        let background = pixel_to_colorf(unsafe { (*face).background } as u64);
+        output
+            .display_list_builder()
+            .push_rect(&common, bounds, background);
+    }
+
+    let foreground = pixel_to_colorf(unsafe { (*face).foreground } as u64);
+
+    output.display_list_builder().push_text(
+        &common,
+        bounds,
+        &glyph_instances,
+        font.font_instance_key,
+        foreground,
+        None,
+    );
+
+    (to - from) as i32
 }
 
 extern "C" fn list(frame: *mut frame, font_spec: LispObject) -> LispObject {
@@ -124,14 +347,178 @@ extern "C" fn list(frame: *mut frame, font_spec: LispObject) -> LispObject {
     match_(frame, font_spec)
 }
 
+/// Emacs symbolic weight names mapped to the numeric scale used by
+/// `font-weight-table`, together with the corresponding `font_kit`
+/// `Weight` (CSS scale) constant. The two scales are unrelated — Emacs
+/// `bold` is 200, CSS `bold` is 700 — so weight is always looked up in
+/// this table (by name, or by nearest Emacs-scale value for a numeric
+/// spec) rather than converted with a formula.
+const WEIGHT_TABLE: &[(&str, f32, f32)] = &[
+    ("thin", 0.0, 100.0),
+    ("extra-light", 40.0, 200.0),
+    ("light", 50.0, 300.0),
+    ("semi-light", 75.0, 350.0),
+    ("normal", 100.0, 400.0),
+    ("regular", 100.0, 400.0),
+    ("medium", 100.0, 500.0),
+    ("semi-bold", 180.0, 600.0),
+    ("bold", 200.0, 700.0),
+    ("extra-bold", 205.0, 800.0),
+    ("black", 210.0, 900.0),
+];
+
+/// Emacs symbolic slant names mapped to the numeric scale used by
+/// `font-slant-table`.
+const SLANT_TABLE: &[(&str, f32)] = &[
+    ("reverse-oblique", 0.0),
+    ("reverse-italic", 10.0),
+    ("normal", 100.0),
+    ("italic", 200.0),
+    ("oblique", 210.0),
+];
+
+/// Emacs symbolic width names mapped to the numeric scale used by
+/// `font-width-table`.
+const WIDTH_TABLE: &[(&str, f32)] = &[
+    ("ultra-condensed", 50.0),
+    ("extra-condensed", 63.0),
+    ("condensed", 75.0),
+    ("semi-condensed", 87.0),
+    ("normal", 100.0),
+    ("semi-expanded", 113.0),
+    ("expanded", 125.0),
+    ("extra-expanded", 150.0),
+    ("ultra-expanded", 200.0),
+];
+
+/// Resolve a `font-spec` slant or width property to its numeric value: a
+/// symbol such as `italic` is looked up in `table`, an already-numeric
+/// value is returned as is.
+fn resolve_style_value(val: LispObject, table: &[(&str, f32)]) -> Option<f32> {
+    if val.is_nil() {
+        return None;
+    }
+
+    if let Some(n) = val.as_fixnum() {
+        return Some(n as f32);
+    }
+
+    let symbol_or_string = val.as_symbol_or_string();
+    let string: LispStringRef = symbol_or_string.into();
+    let name = string.to_string();
+
+    table.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+}
+
+/// The inverse of [`resolve_style_value`]: the symbolic name for a
+/// resolved numeric style value, if the spec carries one.
+fn name_for_value(value: f32, table: &[(&str, f32)]) -> Option<String> {
+    table
+        .iter()
+        .find(|(_, v)| *v == value)
+        .map(|(n, _)| n.to_string())
+}
+
+/// Resolve a `font-spec` weight property to the Emacs name and the
+/// `font_kit` CSS-scale weight it corresponds to. Unlike slant/width,
+/// weight cannot be converted with a formula: the Emacs and CSS scales
+/// disagree even on `normal` (100 vs 400), so an explicit numeric spec
+/// value is matched to its *nearest* named entry on the Emacs scale.
+fn resolve_weight(val: LispObject) -> Option<(&'static str, f32)> {
+    if val.is_nil() {
+        return None;
+    }
+
+    if let Some(n) = val.as_fixnum() {
+        let n = n as f32;
+        return WEIGHT_TABLE
+            .iter()
+            .min_by(|(_, a, _), (_, b, _)| (a - n).abs().partial_cmp(&(b - n).abs()).unwrap())
+            .map(|(name, _, css)| (*name, *css));
+    }
+
+    let symbol_or_string = val.as_symbol_or_string();
+    let string: LispStringRef = symbol_or_string.into();
+    let name = string.to_string();
+
+    WEIGHT_TABLE
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(n, _, css)| (*n, *css))
+}
+
+/// The XLFD slant field is a single-letter code, unlike the full words
+/// used by `SLANT_TABLE` (and, by luck, by the XLFD weight/setwidth
+/// fields). Normalize it so `resolve_style_value` can find it.
+fn xlfd_slant_name(code: &str) -> &str {
+    match code {
+        "r" => "normal",
+        "i" => "italic",
+        "o" => "oblique",
+        "ri" => "reverse-italic",
+        "ro" => "reverse-oblique",
+        other => other,
+    }
+}
+
+/// Number of hyphen-separated fields in an XLFD name, not counting the
+/// leading dash: foundry, family, weight, slant, setwidth, addstyle,
+/// pixelsize, pointsize, resx, resy, spacing, avgwidth, registry, encoding.
+const XLFD_FIELD_COUNT: usize = 14;
+
+/// Split an XLFD font name into its 14 fields. A family containing a
+/// literal hyphen throws off a naive split, so when there are too many
+/// fields the extras are rejoined back into the family field.
+fn split_xlfd_fields(name: &str) -> Option<Vec<String>> {
+    let rest = name.strip_prefix('-')?;
+    let mut fields: Vec<String> = rest.split('-').map(|s| s.to_string()).collect();
+
+    while fields.len() > XLFD_FIELD_COUNT {
+        let extra = fields.remove(2);
+        fields[1] = format!("{}-{}", fields[1], extra);
+    }
+
+    if fields.len() == XLFD_FIELD_COUNT {
+        Some(fields)
+    } else {
+        None
+    }
+}
+
 extern "C" fn match_(_f: *mut frame, spec: LispObject) -> LispObject {
     let font_spec = LispFontLike(spec);
+
+    if let Some(name) = font_spec.get_name() {
+        if !font_spec.parse_xlfd(&name) {
+            font_spec.parse_fontconfig(&name);
+        }
+    }
+
     let family = font_spec.get_family();
 
+    let weight = resolve_weight(font_spec.aref(font_property_index::FONT_WEIGHT_INDEX));
+    let slant = resolve_style_value(
+        font_spec.aref(font_property_index::FONT_SLANT_INDEX),
+        SLANT_TABLE,
+    );
+    let width = resolve_style_value(
+        font_spec.aref(font_property_index::FONT_WIDTH_INDEX),
+        WIDTH_TABLE,
+    );
+
+    let mut properties = Properties::new();
+    properties.weight = Weight(weight.map(|(_, css)| css).unwrap_or(Weight::NORMAL.0));
+    properties.style = match slant.unwrap_or(100.0) {
+        s if s >= 210.0 => Style::Oblique,
+        s if s >= 200.0 => Style::Italic,
+        _ => Style::Normal,
+    };
+    properties.stretch = Stretch(width.unwrap_or(100.0) / 100.0);
+
     let font = family
         .and_then(|f| {
             SystemSource::new()
-                .select_best_match(&[f], &Properties::new())
+                .select_best_match(&[f], &properties)
                 .ok()
         })
         .and_then(|h| h.load().ok());
@@ -149,11 +536,25 @@ extern "C" fn match_(_f: *mut frame, spec: LispObject) -> LispObject {
                 Fmake_symbol(LispObject::from(family_name))
             });
 
-            let full_name: &str = &f.full_name();
-            // set name
+            // set style, as symbols, but only for what the spec actually
+            // requested — leave the rest nil rather than stamping
+            // defaults over every face.
+            if let Some((weight_name, _)) = weight {
+                entity.set_symbol_field(font_property_index::FONT_WEIGHT_INDEX, weight_name);
+            }
+            if let Some(slant_name) = slant.and_then(|v| name_for_value(v, SLANT_TABLE)) {
+                entity.set_symbol_field(font_property_index::FONT_SLANT_INDEX, &slant_name);
+            }
+            if let Some(width_name) = width.and_then(|v| name_for_value(v, WIDTH_TABLE)) {
+                entity.set_symbol_field(font_property_index::FONT_WIDTH_INDEX, &width_name);
+            }
+
+            // set name to the canonical fontconfig name, for display in
+            // the mode line and `describe-font`.
+            let canonical_name = entity.unparse_fontconfig();
             entity.aset(
                 font_property_index::FONT_NAME_INDEX,
-                LispObject::from(full_name),
+                LispObject::from(canonical_name.as_str()),
             );
 
             let postscript_name: &str = &f
@@ -168,6 +569,18 @@ extern "C" fn match_(_f: *mut frame, spec: LispObject) -> LispObject {
                 )
             });
 
+            // set registry, so fontset fallback can tell which charsets
+            // this font actually covers.
+            if entity
+                .aref(font_property_index::FONT_REGISTRY_INDEX)
+                .is_nil()
+            {
+                entity.set_symbol_field(
+                    font_property_index::FONT_REGISTRY_INDEX,
+                    detect_registry(&f),
+                );
+            }
+
             unsafe { Fcons(entity.as_lisp_object(), Qnil) }
         }
         None => Qnil,
@@ -176,7 +589,39 @@ extern "C" fn match_(_f: *mut frame, spec: LispObject) -> LispObject {
 
 #[allow(unused_variables)]
 extern "C" fn list_family(f: *mut frame) -> LispObject {
-    unimplemented!();
+    let families = SystemSource::new()
+        .all_families()
+        .unwrap_or_else(|_| Vec::new());
+
+    families.iter().fold(Qnil, |list, family_name| unsafe {
+        Fcons(Fmake_symbol(LispObject::from(family_name.as_str())), list)
+    })
+}
+
+/// Representative codepoints from distinct Unicode blocks/scripts. A font
+/// needs a glyph for only one of these to be treated as carrying a real
+/// Unicode cmap — CJK-only, Arabic, or emoji/symbol fonts have none of the
+/// Latin codepoints a single-probe check would rely on, but will match one
+/// of these.
+const UNICODE_COVERAGE_PROBES: &[char] = &[
+    'A', '\u{2022}', '\u{4e2d}', '\u{3042}', '\u{ac00}', '\u{0627}', '\u{05d0}', '\u{0391}',
+    '\u{0410}', '\u{1f600}',
+];
+
+/// Probe a loaded font's coverage to decide which registry/encoding it
+/// should be advertised under. A font with a glyph for any representative
+/// Unicode codepoint is advertised as `iso10646-1`, regardless of whether
+/// it happens to also cover Latin; anything else is an `unknown`-coverage
+/// font.
+fn detect_registry(font: &Font) -> &'static str {
+    if UNICODE_COVERAGE_PROBES
+        .iter()
+        .any(|&c| font.glyph_for_char(c).is_some())
+    {
+        "iso10646-1"
+    } else {
+        "unknown"
+    }
 }
 
 #[repr(C)]
@@ -185,10 +630,17 @@ pub struct WRFont {
     pub font: font,
     // webrender font key
     pub font_key: FontKey,
+    // webrender font instance key, at this font's pixel size. Created once
+    // in `open` and reused by every `draw` call instead of minting a fresh
+    // instance per run.
+    pub font_instance_key: FontInstanceKey,
     // font-kit font
     pub metrics: Metrics,
 
     pub font_backend: ManuallyDrop<Font>,
+
+    // registry/encoding this font declares coverage for, e.g. "iso10646-1"
+    pub registry: &'static str,
 }
 
 impl WRFont {
@@ -275,6 +727,7 @@ extern "C" fn open(frame: *mut frame, font_entity: LispObject, pixel_size: i32)
         .as_webrender_font();
 
     wr_font.font_backend = ManuallyDrop::new(font.load().unwrap());
+    wr_font.registry = detect_registry(&wr_font.font_backend);
 
     let (font_metrics, font_advance) = {
         let font = font.load().unwrap();
@@ -297,6 +750,7 @@ extern "C" fn open(frame: *mut frame, font_entity: LispObject, pixel_size: i32)
     wr_font.font.driver = FONT_DRIVER.clone().as_mut();
 
     wr_font.font_key = font_key;
+    wr_font.font_instance_key = output.add_font_instance(font_key, pixel_size as i32);
 
     font_object.as_lisp_object()
 }
@@ -306,12 +760,14 @@ extern "C" fn close(_font: *mut font) {}
 extern "C" fn encode_char(font: *mut font, c: i32) -> u32 {
     let font: WRFontRef = font.into();
 
+    // Let the font's actual cmap decide — gating on `registry` here would
+    // reject glyphs the font genuinely has whenever it isn't advertised as
+    // `iso10646-1`, which defeats fontset fallback rather than helping it.
     std::char::from_u32(c as u32)
         .and_then(|c| font.glyph_for_char(c))
         .unwrap_or(FONT_INVALID_CODE)
 }
 
-#[allow(unused_variables)]
 extern "C" fn text_extents(
     font: *mut font,
     code: *mut u32,
@@ -320,11 +776,49 @@ extern "C" fn text_extents(
 ) {
     let font: WRFontRef = font.into();
 
+    let scale = font.font.pixel_size as f32 / font.metrics.units_per_em as f32;
+    let glyphs = unsafe { std::slice::from_raw_parts(code, nglyphs as usize) };
+
+    let mut total_width: i32 = 0;
+    let mut lbearing: i32 = 0;
+    let mut rbearing: i32 = 0;
+    let mut ascent: i32 = font.font.ascent;
+    let mut descent: i32 = font.font.descent;
+
+    for (i, &glyph) in glyphs.iter().enumerate() {
+        let advance = font
+            .font_backend
+            .advance(glyph)
+            .map(|a| (a.x() * scale) as i32)
+            .unwrap_or(font.font.average_width);
+
+        let bounds = font.font_backend.typographic_bounds(glyph).ok();
+
+        let (glyph_lbearing, glyph_rbearing, glyph_ascent, glyph_descent) = match bounds {
+            Some(b) => (
+                (b.min_x() * scale) as i32,
+                (b.max_x() * scale) as i32,
+                (b.max_y() * scale).round() as i32,
+                (-b.min_y() * scale).round() as i32,
+            ),
+            None => (0, advance, font.font.ascent, font.font.descent),
+        };
+
+        if i == 0 {
+            lbearing = total_width + glyph_lbearing;
+        }
+        rbearing = rbearing.max(total_width + glyph_rbearing);
+        ascent = ascent.max(glyph_ascent);
+        descent = descent.max(glyph_descent);
+
+        total_width += advance;
+    }
+
     unsafe {
-        (*metrics).lbearing = 10;
-        (*metrics).rbearing = 10;
-        (*metrics).width = font.font.average_width as i16;
-        (*metrics).ascent = font.font.ascent as i16;
-        (*metrics).descent = font.font.descent as i16;
+        (*metrics).lbearing = lbearing as i16;
+        (*metrics).rbearing = rbearing as i16;
+        (*metrics).width = total_width as i16;
+        (*metrics).ascent = ascent as i16;
+        (*metrics).descent = descent as i16;
     }
 }